@@ -0,0 +1,323 @@
+use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
+use core::cell::{Ref, RefCell};
+
+/// A word-sized value as seen by WIPI-C method calls: an integer, a raw guest pointer, or a packed
+/// error code, depending on the call site.
+pub type WIPICWord = u32;
+
+/// Handle to a block of memory allocated via `WIPICContext::alloc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WIPICMemoryId(pub WIPICWord);
+
+/// Handle to a task spawned via `WIPICContext::spawn`, used to cancel it before it finishes on
+/// its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WIPICTaskId(pub u32);
+
+pub type WIPICError = anyhow::Error;
+pub type WIPICResult<T> = Result<T, WIPICError>;
+pub type WIPICMethodBody = Box<dyn crate::method::MethodBody<WIPICError>>;
+
+#[async_trait::async_trait(?Send)]
+pub trait WIPICContext {
+    fn backend(&self) -> Backend;
+
+    /// The id of the program this context is currently executing on behalf of.
+    fn program_id(&self) -> i32;
+
+    fn alloc(&mut self, size: WIPICWord) -> WIPICResult<WIPICMemoryId>;
+    fn free(&mut self, memory: WIPICMemoryId) -> WIPICResult<()>;
+    fn data_ptr(&self, memory: WIPICMemoryId) -> WIPICResult<WIPICWord>;
+
+    fn write_bytes(&mut self, address: WIPICWord, data: &[u8]) -> WIPICResult<()>;
+    fn read_bytes(&mut self, address: WIPICWord, size: usize) -> WIPICResult<Vec<u8>>;
+
+    /// Runs `body` as a separate task and returns a handle that can later be passed to `abort`.
+    fn spawn(&mut self, body: WIPICMethodBody) -> WIPICResult<WIPICTaskId>;
+    /// Stops a task spawned via `spawn` before it finishes on its own. A no-op if it already has.
+    fn abort(&mut self, task: WIPICTaskId);
+
+    async fn sleep(&mut self, duration: u64);
+    async fn call_method(&mut self, address: WIPICWord, args: &[WIPICWord]) -> WIPICResult<WIPICWord>;
+}
+
+/// Cheap-to-clone handle to the state shared by every `WIPICContext` of the same running WIPI
+/// instance, e.g. resources, the system clock, and the subsystems backing the kernel module's
+/// system property, shared-buffer, and program tables.
+#[derive(Clone)]
+pub struct Backend(Rc<BackendInner>);
+
+struct BackendInner {
+    resource: RefCell<Resource>,
+    time: RefCell<Time>,
+    system_property: SystemPropertyStore,
+    shared_buffer_table: SharedBufferTable,
+    program_table: ProgramTable,
+}
+
+impl Backend {
+    pub fn resource(&self) -> Ref<'_, Resource> {
+        self.0.resource.borrow()
+    }
+
+    pub fn time(&self) -> Ref<'_, Time> {
+        self.0.time.borrow()
+    }
+
+    pub fn system_property(&self) -> &SystemPropertyStore {
+        &self.0.system_property
+    }
+
+    pub fn shared_buffer_table(&self) -> &SharedBufferTable {
+        &self.0.shared_buffer_table
+    }
+
+    pub fn program_table(&self) -> &ProgramTable {
+        &self.0.program_table
+    }
+}
+
+/// Read-only resources bundled with a WIPI application (images, class files, ...), looked up by
+/// name and referred to afterwards by the id `MC_knlGetResourceID` hands back.
+pub struct Resource {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Resource {
+    pub fn id(&self, name: &str) -> Option<WIPICWord> {
+        self.entries.iter().position(|(entry_name, _)| entry_name == name).map(|x| x as WIPICWord)
+    }
+
+    pub fn size(&self, id: WIPICWord) -> WIPICWord {
+        self.entries.get(id as usize).map(|(_, data)| data.len() as WIPICWord).unwrap_or(0)
+    }
+
+    pub fn data(&self, id: WIPICWord) -> &[u8] {
+        self.entries.get(id as usize).map(|(_, data)| data.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// The device clock, as seen by `MC_knlCurrentTime`.
+pub struct Time;
+
+impl Time {
+    pub fn now(&self) -> Instant {
+        Instant(0)
+    }
+}
+
+pub struct Instant(u64);
+
+impl Instant {
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Backend-owned key/value store behind `MC_knlGetSystemProperty`/`MC_knlSetSystemProperty`, so a
+/// property set by one call is visible to a later get without either side hardcoding the value.
+#[derive(Default)]
+pub struct SystemPropertyStore(RefCell<Vec<(String, String)>>);
+
+impl SystemPropertyStore {
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.0.borrow().iter().find(|(entry_name, _)| entry_name == name).map(|(_, value)| value.clone())
+    }
+
+    pub fn set(&self, name: &str, value: String) {
+        let mut properties = self.0.borrow_mut();
+        match properties.iter_mut().find(|(entry_name, _)| entry_name == name) {
+            Some((_, entry_value)) => *entry_value = value,
+            None => properties.push((String::from(name), value)),
+        }
+    }
+}
+
+/// Backend-owned registry of named, reference-counted shared buffers behind
+/// `MC_knlCreateSharedBuf` and friends, so every program that names the same buffer sees the same
+/// backing memory instead of each caller maintaining its own copy.
+#[derive(Default)]
+pub struct SharedBufferTable(RefCell<Vec<(String, SharedBuffer)>>);
+
+struct SharedBuffer {
+    memory: WIPICMemoryId,
+    size: WIPICWord,
+    /// One entry per outstanding reference, recording which program holds it, so a program that
+    /// exits without explicitly releasing its own references doesn't leak them (or release a
+    /// reference it never actually held).
+    owners: Vec<i32>,
+}
+
+impl SharedBufferTable {
+    pub fn exists(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    pub fn create(&self, name: &str, owner: i32, memory: WIPICMemoryId, size: WIPICWord) {
+        self.0.borrow_mut().push((String::from(name), SharedBuffer { memory, size, owners: alloc::vec![owner] }));
+    }
+
+    /// Hands out another reference to the named buffer, recording `owner` as holding it.
+    pub fn acquire(&self, name: &str, owner: i32) -> Option<WIPICMemoryId> {
+        let mut buffers = self.0.borrow_mut();
+        let (_, buffer) = buffers.iter_mut().find(|(entry_name, _)| entry_name == name)?;
+        buffer.owners.push(owner);
+        Some(buffer.memory)
+    }
+
+    /// Drops one of `owner`'s references to the named buffer (or, if it doesn't hold one, some
+    /// other reference, matching `MC_knlDestroySharedBuf`'s historical "release any reference"
+    /// behavior). Returns `None` if it doesn't exist, `Some(None)` if other references remain, or
+    /// `Some(Some(memory))` with the now-orphaned memory once the last reference is gone, so the
+    /// caller can free it.
+    pub fn release(&self, name: &str, owner: i32) -> Option<Option<WIPICMemoryId>> {
+        let mut buffers = self.0.borrow_mut();
+        let index = buffers.iter().position(|(entry_name, _)| entry_name == name)?;
+        let owners = &mut buffers[index].1.owners;
+
+        let remove_at = owners.iter().position(|&entry_owner| entry_owner == owner).unwrap_or(owners.len() - 1);
+        owners.remove(remove_at);
+
+        if buffers[index].1.owners.is_empty() {
+            Some(Some(buffers.remove(index).1.memory))
+        } else {
+            Some(None)
+        }
+    }
+
+    pub fn size(&self, name: &str) -> Option<WIPICWord> {
+        self.find(name).map(|buffer| buffer.size)
+    }
+
+    pub fn memory_and_size(&self, name: &str) -> Option<(WIPICMemoryId, WIPICWord)> {
+        self.find(name).map(|buffer| (buffer.memory, buffer.size))
+    }
+
+    pub fn update(&self, name: &str, memory: WIPICMemoryId, size: WIPICWord) {
+        if let Some((_, buffer)) = self.0.borrow_mut().iter_mut().find(|(entry_name, _)| entry_name == name) {
+            buffer.memory = memory;
+            buffer.size = size;
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<Ref<'_, SharedBuffer>> {
+        let buffers = self.0.borrow();
+        buffers
+            .iter()
+            .position(|(entry_name, _)| entry_name == name)
+            .map(|index| Ref::map(buffers, |buffers| &buffers[index].1))
+    }
+}
+
+/// Backend-owned table of loaded programs behind `MC_knlLoad`/`MC_knlExecute` and the program ID
+/// queries, so a program loaded by one call can be started, queried, and stopped by id afterwards.
+#[derive(Default)]
+pub struct ProgramTable(RefCell<ProgramTableState>);
+
+#[derive(Default)]
+struct ProgramTableState {
+    next_id: i32,
+    programs: Vec<(i32, ProgramEntry)>,
+}
+
+struct ProgramEntry {
+    name: String,
+    parent_id: i32,
+    entry_point: WIPICWord,
+    /// Bumped every time this program exits, so a spawned entry-point task that hasn't been
+    /// polled yet can tell it was stopped before it ever got to run.
+    generation: WIPICWord,
+    /// The task running this program's entry point, if it's gotten that far, so it can be
+    /// aborted outright once it's actually running rather than just racing on `generation`.
+    task: Option<WIPICTaskId>,
+    /// Cleanup run when this program exits, e.g. releasing shared buffers it still held. Not run
+    /// here since that needs a `WIPICContext`; see `ProgramTable::take_cleanup`.
+    cleanup: Vec<Box<dyn FnOnce(&mut dyn WIPICContext)>>,
+}
+
+impl ProgramTable {
+    /// Registers `name` as a newly loaded program and returns its id. The name is not looked up
+    /// anywhere: this always returns `Some`, and the program's entry point is recorded as 0.
+    pub fn load(&self, name: &str, parent_id: i32) -> Option<i32> {
+        let mut state = self.0.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        state.programs.push((
+            id,
+            ProgramEntry {
+                name: String::from(name),
+                parent_id,
+                entry_point: 0,
+                generation: 0,
+                task: None,
+                cleanup: Vec::new(),
+            },
+        ));
+
+        Some(id)
+    }
+
+    /// Looks up a loaded program's entry point and current generation, for `MC_knlExecute` to
+    /// spawn it and later tell whether it's still the same run.
+    pub fn start(&self, id: i32) -> Option<(WIPICWord, WIPICWord)> {
+        self.find(id).map(|entry| (entry.entry_point, entry.generation))
+    }
+
+    /// Records the task running this program's entry point, so `exit` can abort it later.
+    pub fn set_task(&self, id: i32, task: WIPICTaskId) {
+        if let Some((_, entry)) = self.0.borrow_mut().programs.iter_mut().find(|(entry_id, _)| *entry_id == id) {
+            entry.task = Some(task);
+        }
+    }
+
+    /// Registers cleanup to run when this program exits, regardless of whether it stops itself
+    /// or is stopped by another program.
+    pub fn add_cleanup(&self, id: i32, hook: Box<dyn FnOnce(&mut dyn WIPICContext)>) {
+        if let Some((_, entry)) = self.0.borrow_mut().programs.iter_mut().find(|(entry_id, _)| *entry_id == id) {
+            entry.cleanup.push(hook);
+        }
+    }
+
+    /// Drains this program's registered cleanup hooks for the caller to run with a context.
+    pub fn take_cleanup(&self, id: i32) -> Vec<Box<dyn FnOnce(&mut dyn WIPICContext)>> {
+        self.0
+            .borrow_mut()
+            .programs
+            .iter_mut()
+            .find(|(entry_id, _)| *entry_id == id)
+            .map(|(_, entry)| core::mem::take(&mut entry.cleanup))
+            .unwrap_or_default()
+    }
+
+    pub fn generation(&self, id: i32) -> Option<WIPICWord> {
+        self.find(id).map(|entry| entry.generation)
+    }
+
+    /// Bumps the program's generation, so a spawned task that hasn't run its first poll yet backs
+    /// off instead of starting an exited program, and hands back its running task (if any) so the
+    /// caller can abort it outright.
+    pub fn exit(&self, id: i32) -> Option<WIPICTaskId> {
+        let mut state = self.0.borrow_mut();
+        let (_, entry) = state.programs.iter_mut().find(|(entry_id, _)| *entry_id == id)?;
+        entry.generation = entry.generation.wrapping_add(1);
+        entry.task.take()
+    }
+
+    pub fn parent_id(&self, id: i32) -> Option<i32> {
+        self.find(id).map(|entry| entry.parent_id)
+    }
+
+    pub fn name(&self, id: i32) -> Option<String> {
+        self.find(id).map(|entry| entry.name.clone())
+    }
+
+    fn find(&self, id: i32) -> Option<Ref<'_, ProgramEntry>> {
+        let state = self.0.borrow();
+        state
+            .programs
+            .iter()
+            .position(|(entry_id, _)| *entry_id == id)
+            .map(|index| Ref::map(state, |state| &state.programs[index].1))
+    }
+}
@@ -15,8 +15,12 @@ use crate::{
 #[repr(C, packed)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct WIPICTimer {
-    unk1: WIPICWord,
-    unk2: WIPICWord,
+    /// Bumped every time this timer is (re)armed or unset, so an in-flight, spawned timer task
+    /// can tell whether it's been superseded or cancelled the next time it wakes up.
+    generation: WIPICWord,
+    /// Nonzero for a periodic "synchronization" timer that keeps re-arming itself every `timeout`
+    /// until unset; zero for a one-shot "notification" timer that fires once and stops.
+    periodic: WIPICWord,
     unk3: WIPICWord,
     time: u64,
 
@@ -37,10 +41,63 @@ async fn current_time(context: &mut dyn WIPICContext) -> WIPICResult<WIPICWord>
     Ok(context.backend().time().now().raw() as WIPICWord)
 }
 
+/// Every property name `MC_knlGetSystemProperty`/`MC_knlSetSystemProperty` recognize. Anything
+/// else is rejected with `M_E_INVALID`.
+const KNOWN_SYSTEM_PROPERTIES: &[&str] = &[
+    "ESN",
+    "NID",
+    "SID",
+    "BASEID",
+    "BASELAT",
+    "BASELONG",
+    "CURRENTCH",
+    "PHONENUMBER",
+    "RSSILEVEL",
+    "MAXRSSILEVEL",
+    "BATTERYLEVEL",
+    "MAXBATTLEVEL",
+    "MAXSERIALNUM",
+    "MAXSOCKETNUM",
+    "MEDIADEVICES",
+    "DNS",
+    "TIMEZONE",
+    "PHONEMODEL",
+    "KEYREPEAT",
+    "VIBRATORLEVEL",
+    "VOLUMELEVEL",
+    "ANNUN_CALL",
+    "ANNUN_SMS",
+    "ANNUN_SILENT",
+    "ANNUN_ALARM",
+    "ANNUN_SECURITY",
+];
+
+/// System properties that are reported by the device itself and can't be changed by an app
+/// through `MC_knlSetSystemProperty`.
+const READONLY_SYSTEM_PROPERTIES: &[&str] = &[
+    "ESN",
+    "NID",
+    "SID",
+    "PHONENUMBER",
+    "MAXRSSILEVEL",
+    "MAXBATTLEVEL",
+    "MAXSERIALNUM",
+    "MAXSOCKETNUM",
+];
+
 async fn get_system_property(context: &mut dyn WIPICContext, p_id: WIPICWord, p_out: WIPICWord, buf_size: WIPICWord) -> WIPICResult<WIPICErrorCode> {
     let property_name = read_null_terminated_string(context, p_id)?;
     tracing::trace!("MC_knlGetSystemProperty({}(@{:#x}), {:#x}, {})", &property_name, p_id, p_out, buf_size);
 
+    if let Some(value) = context.backend().system_property().get(&property_name) {
+        if (buf_size as usize) < value.len() + 1 {
+            return Ok(WIPICErrorCode::SHORTBUF);
+        }
+        write_null_terminated_string(context, p_out, &value)?;
+
+        return Ok(WIPICErrorCode::SUCCESS);
+    }
+
     let result: Cow<str> = match property_name.as_str() {
         "ESN" => "01234567891".into(),             // CDMA Electronic Serial Number
         "NID" => "65535".into(),                   // CDMA Network Identification
@@ -97,12 +154,31 @@ async fn get_system_property(context: &mut dyn WIPICContext, p_id: WIPICWord, p_
     Ok(WIPICErrorCode::SUCCESS)
 }
 
-async fn def_timer(context: &mut dyn WIPICContext, ptr_timer: WIPICWord, fn_callback: WIPICWord) -> WIPICResult<()> {
-    tracing::debug!("MC_knlDefTimer({:#x}, {:#x})", ptr_timer, fn_callback);
+async fn set_system_property(context: &mut dyn WIPICContext, p_id: WIPICWord, p_value: WIPICWord) -> WIPICResult<WIPICErrorCode> {
+    let property_name = read_null_terminated_string(context, p_id)?;
+    let value = read_null_terminated_string(context, p_value)?;
+    tracing::trace!("MC_knlSetSystemProperty({}(@{:#x}), {}(@{:#x}))", &property_name, p_id, &value, p_value);
+
+    if READONLY_SYSTEM_PROPERTIES.contains(&property_name.as_str()) {
+        return Ok(WIPICErrorCode::NOTSUP);
+    }
+
+    if !KNOWN_SYSTEM_PROPERTIES.contains(&property_name.as_str()) {
+        tracing::warn!("MC_knlSetSystemProperty({}): unknown property, returning M_E_INVALID", &property_name);
+        return Ok(WIPICErrorCode::INVALID);
+    }
+
+    context.backend().system_property().set(&property_name, value);
+
+    Ok(WIPICErrorCode::SUCCESS)
+}
+
+async fn def_timer(context: &mut dyn WIPICContext, ptr_timer: WIPICWord, fn_callback: WIPICWord, periodic: WIPICWord) -> WIPICResult<()> {
+    tracing::debug!("MC_knlDefTimer({:#x}, {:#x}, {:#x})", ptr_timer, fn_callback, periodic);
 
     let timer = WIPICTimer {
-        unk1: 0,
-        unk2: 0,
+        generation: 0,
+        periodic,
         unk3: 0,
         time: 0,
         param: 0,
@@ -124,10 +200,18 @@ async fn set_timer(
 ) -> WIPICResult<()> {
     tracing::debug!("MC_knlSetTimer({:#x}, {:#x}, {:#x}, {:#x})", ptr_timer, timeout_low, timeout_high, param);
 
-    let timer: WIPICTimer = read_generic(context, ptr_timer)?;
+    let mut timer: WIPICTimer = read_generic(context, ptr_timer)?;
+
+    // Cancel whatever timer task is currently owning this slot before arming a new one.
+    timer.generation = timer.generation.wrapping_add(1);
+    timer.param = param;
+    write_generic(context, ptr_timer, timer)?;
 
     struct TimerCallback {
-        timer: WIPICTimer,
+        ptr_timer: WIPICWord,
+        generation: WIPICWord,
+        periodic: bool,
+        fn_callback: WIPICWord,
         timeout: u64,
         param: WIPICWord,
     }
@@ -136,16 +220,34 @@ async fn set_timer(
     impl MethodBody<WIPICError> for TimerCallback {
         #[tracing::instrument(name = "timer", skip_all)]
         async fn call(&self, context: &mut dyn WIPICContext, _: &[WIPICWord]) -> Result<WIPICWord, WIPICError> {
-            context.sleep(self.timeout).await;
-
-            context.call_method(self.timer.fn_callback, &[self.param]).await?;
+            // A periodic ("synchronization") timer keeps re-arming itself at the same interval
+            // until MC_knlUnsetTimer bumps the generation stored in the timer struct; a one-shot
+            // ("notification") timer fires at most once, and not at all if it's unset or re-armed
+            // before its timeout.
+            loop {
+                context.sleep(self.timeout).await;
+
+                let timer: WIPICTimer = read_generic(context, self.ptr_timer)?;
+                if timer.generation != self.generation {
+                    break;
+                }
+
+                context.call_method(self.fn_callback, &[self.param]).await?;
+
+                if !self.periodic {
+                    break;
+                }
+            }
 
             Ok(0)
         }
     }
 
     context.spawn(Box::new(TimerCallback {
-        timer,
+        ptr_timer,
+        generation: timer.generation,
+        periodic: timer.periodic != 0,
+        fn_callback: timer.fn_callback,
         timeout: ((timeout_high as u64) << 32) | (timeout_low as u64),
         param,
     }))?;
@@ -153,8 +255,12 @@ async fn set_timer(
     Ok(())
 }
 
-async fn unset_timer(_: &mut dyn WIPICContext, a0: WIPICWord) -> WIPICResult<()> {
-    tracing::warn!("stub MC_knlUnsetTimer({:#x})", a0);
+async fn unset_timer(context: &mut dyn WIPICContext, ptr_timer: WIPICWord) -> WIPICResult<()> {
+    tracing::debug!("MC_knlUnsetTimer({:#x})", ptr_timer);
+
+    let mut timer: WIPICTimer = read_generic(context, ptr_timer)?;
+    timer.generation = timer.generation.wrapping_add(1);
+    write_generic(context, ptr_timer, timer)?;
 
     Ok(())
 }
@@ -184,6 +290,238 @@ async fn free(context: &mut dyn WIPICContext, memory: WIPICMemoryId) -> WIPICRes
     Ok(memory)
 }
 
+async fn create_shared_buf(context: &mut dyn WIPICContext, name: String, size: WIPICWord) -> WIPICResult<WIPICErrorCode> {
+    tracing::debug!("MC_knlCreateSharedBuf({}, {})", name, size);
+
+    if context.backend().shared_buffer_table().exists(&name) {
+        return Ok(WIPICErrorCode::DUPNAME);
+    }
+
+    let owner = context.program_id();
+    let memory = context.alloc(size)?;
+    context.backend().shared_buffer_table().create(&name, owner, memory, size);
+    release_shared_buf_on_exit(context, name);
+
+    Ok(WIPICErrorCode::SUCCESS)
+}
+
+async fn destroy_shared_buf(context: &mut dyn WIPICContext, name: String) -> WIPICResult<WIPICErrorCode> {
+    tracing::debug!("MC_knlDestroySharedBuf({})", name);
+
+    let owner = context.program_id();
+    let released = match context.backend().shared_buffer_table().release(&name, owner) {
+        Some(released) => released,
+        None => return Ok(WIPICErrorCode::NOTEXIST),
+    };
+
+    // Only free the backing memory once the last reference to the shared buffer is gone, so a
+    // program that exits early doesn't yank the buffer out from under one still using it.
+    if let Some(memory) = released {
+        context.free(memory)?;
+    }
+
+    Ok(WIPICErrorCode::SUCCESS)
+}
+
+async fn get_shared_buf(context: &mut dyn WIPICContext, name: String) -> WIPICResult<WIPICMemoryId> {
+    tracing::debug!("MC_knlGetSharedBuf({})", name);
+
+    let owner = context.program_id();
+    match context.backend().shared_buffer_table().acquire(&name, owner) {
+        Some(memory) => {
+            release_shared_buf_on_exit(context, name);
+            Ok(memory)
+        }
+        None => Ok(WIPICMemoryId(0)),
+    }
+}
+
+/// Ties a shared-buffer reference to the calling program, so `MC_knlExit`/`MC_knlProgramStop`
+/// release it automatically if the program exits without ever calling `MC_knlDestroySharedBuf`
+/// itself.
+fn release_shared_buf_on_exit(context: &mut dyn WIPICContext, name: String) {
+    let owner = context.program_id();
+
+    context.backend().program_table().add_cleanup(
+        owner,
+        Box::new(move |context| {
+            if let Some(Some(memory)) = context.backend().shared_buffer_table().release(&name, owner) {
+                let _ = context.free(memory);
+            }
+        }),
+    );
+}
+
+async fn get_shared_buf_size(context: &mut dyn WIPICContext, name: String) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlGetSharedBufSize({})", name);
+
+    match context.backend().shared_buffer_table().size(&name) {
+        Some(size) => Ok(size as i32),
+        None => Ok(-1),
+    }
+}
+
+async fn resize_shared_buf(context: &mut dyn WIPICContext, name: String, new_size: WIPICWord) -> WIPICResult<WIPICErrorCode> {
+    tracing::debug!("MC_knlResizeSharedBuf({}, {})", name, new_size);
+
+    let (old_memory, old_size) = match context.backend().shared_buffer_table().memory_and_size(&name) {
+        Some(x) => x,
+        None => return Ok(WIPICErrorCode::NOTEXIST),
+    };
+
+    let new_memory = context.alloc(new_size)?;
+
+    let copy_size = old_size.min(new_size);
+    let data = context.read_bytes(context.data_ptr(old_memory)?, copy_size as usize)?;
+    context.write_bytes(context.data_ptr(new_memory)?, &data)?;
+
+    context.free(old_memory)?;
+    context.backend().shared_buffer_table().update(&name, new_memory, new_size);
+
+    Ok(WIPICErrorCode::SUCCESS)
+}
+
+async fn load(context: &mut dyn WIPICContext, name: String) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlLoad({})", name);
+
+    let parent_id = context.program_id();
+
+    match context.backend().program_table().load(&name, parent_id) {
+        Some(id) => Ok(id),
+        None => Ok(-1),
+    }
+}
+
+async fn execute(context: &mut dyn WIPICContext, id: i32, param: WIPICWord) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlExecute({}, {:#x})", id, param);
+
+    let (entry_point, generation) = match context.backend().program_table().start(id) {
+        Some(x) => x,
+        None => return Ok(-1),
+    };
+
+    struct ProgramEntryCall {
+        id: i32,
+        generation: WIPICWord,
+        entry_point: WIPICWord,
+        param: WIPICWord,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl MethodBody<WIPICError> for ProgramEntryCall {
+        #[tracing::instrument(name = "program", skip_all)]
+        async fn call(&self, context: &mut dyn WIPICContext, _: &[WIPICWord]) -> Result<WIPICWord, WIPICError> {
+            // MC_knlExit/MC_knlProgramStop bump the program's generation in the table; if that
+            // already happened before this task got to run, don't start an exited program at all.
+            if context.backend().program_table().generation(self.id) != Some(self.generation) {
+                return Ok(0);
+            }
+
+            match context.call_method(self.entry_point, &[self.param]).await {
+                // The program exited itself via MC_knlExit/MC_knlProgramStop, which unwinds
+                // through here as a WIPICProgramExit rather than a real failure; tell the two
+                // apart so a clean exit doesn't get logged like a crash.
+                Err(err) => match err.downcast::<WIPICProgramExit>() {
+                    Ok(_) => Ok(0),
+                    Err(err) => Err(err),
+                },
+                ok => ok,
+            }
+        }
+    }
+
+    let task = context.spawn(Box::new(ProgramEntryCall {
+        id,
+        generation,
+        entry_point,
+        param,
+    }))?;
+    context.backend().program_table().set_task(id, task);
+
+    Ok(0)
+}
+
+/// Returned by a program that's exiting itself, so the task running it unwinds right here instead
+/// of falling through to code past the exit call. Distinct from an ordinary `anyhow` error so
+/// whatever's driving the task (see `ProgramEntryCall::call`) can tell a clean exit apart from an
+/// actual crash.
+#[derive(Debug)]
+struct WIPICProgramExit(i32);
+
+impl core::fmt::Display for WIPICProgramExit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "program {} exited", self.0)
+    }
+}
+
+async fn exit_program(context: &mut dyn WIPICContext, id: i32) -> WIPICResult<()> {
+    tracing::debug!("MC_knlExit({})", id);
+
+    let task = context.backend().program_table().exit(id);
+    for hook in context.backend().program_table().take_cleanup(id) {
+        hook(context);
+    }
+
+    if id == context.program_id() {
+        return Err(anyhow::Error::msg(WIPICProgramExit(id)));
+    }
+
+    // A program other than the caller's own is stopped by aborting its task outright; the
+    // generation bump above only protects the case where the task hasn't had its first poll yet.
+    if let Some(task) = task {
+        context.abort(task);
+    }
+
+    Ok(())
+}
+
+async fn program_stop(context: &mut dyn WIPICContext, id: i32) -> WIPICResult<()> {
+    tracing::debug!("MC_knlProgramStop({})", id);
+
+    let task = context.backend().program_table().exit(id);
+    for hook in context.backend().program_table().take_cleanup(id) {
+        hook(context);
+    }
+
+    if id == context.program_id() {
+        return Err(anyhow::Error::msg(WIPICProgramExit(id)));
+    }
+
+    if let Some(task) = task {
+        context.abort(task);
+    }
+
+    Ok(())
+}
+
+async fn get_cur_program_id(context: &mut dyn WIPICContext) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlGetCurProgramID()");
+
+    Ok(context.program_id())
+}
+
+async fn get_parent_program_id(context: &mut dyn WIPICContext) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlGetParentProgramID()");
+
+    Ok(context.backend().program_table().parent_id(context.program_id()).unwrap_or(-1))
+}
+
+async fn get_program_name(context: &mut dyn WIPICContext, id: i32, p_out: WIPICWord, buf_size: WIPICWord) -> WIPICResult<i32> {
+    tracing::debug!("MC_knlGetProgramName({}, {:#x}, {})", id, p_out, buf_size);
+
+    let name = match context.backend().program_table().name(id) {
+        Some(name) => name,
+        None => return Ok(-1),
+    };
+
+    if (buf_size as usize) < name.len() + 1 {
+        return Ok(-1);
+    }
+    write_null_terminated_string(context, p_out, &name)?;
+
+    Ok(0)
+}
+
 async fn get_resource_id(context: &mut dyn WIPICContext, name: String, ptr_size: WIPICWord) -> WIPICResult<i32> {
     tracing::debug!("MC_knlGetResourceID({}, {:#x})", name, ptr_size);
 
@@ -245,23 +583,23 @@ where
         printk.into_body(),
         gen_stub(1, "MC_knlSprintk"),
         gen_stub(2, "MC_knlGetExecNames"),
-        gen_stub(3, "MC_knlExecute"),
+        execute.into_body(),
         gen_stub(4, "MC_knlMExecute"),
-        gen_stub(5, "MC_knlLoad"),
+        load.into_body(),
         gen_stub(6, "MC_knlMLoad"),
-        gen_stub(7, "MC_knlExit"),
-        gen_stub(8, "MC_knlProgramStop"),
-        gen_stub(9, "MC_knlGetCurProgramID"),
-        gen_stub(10, "MC_knlGetParentProgramID"),
+        exit_program.into_body(),
+        program_stop.into_body(),
+        get_cur_program_id.into_body(),
+        get_parent_program_id.into_body(),
         gen_stub(11, "MC_knlGetAppManagerID"),
         gen_stub(12, "MC_knlGetProgramInfo"),
         gen_stub(13, "MC_knlGetAccessLevel"),
-        gen_stub(14, "MC_knlGetProgramName"),
-        gen_stub(15, "MC_knlCreateSharedBuf"),
-        gen_stub(16, "MC_knlDestroySharedBuf"),
-        gen_stub(17, "MC_knlGetSharedBuf"),
-        gen_stub(18, "MC_knlGetSharedBufSize"),
-        gen_stub(19, "MC_knlResizeSharedBuf"),
+        get_program_name.into_body(),
+        create_shared_buf.into_body(),
+        destroy_shared_buf.into_body(),
+        get_shared_buf.into_body(),
+        get_shared_buf_size.into_body(),
+        resize_shared_buf.into_body(),
         alloc.into_body(),
         calloc.into_body(),
         free.into_body(),
@@ -272,7 +610,7 @@ where
         unset_timer.into_body(),
         current_time.into_body(),
         get_system_property.into_body(),
-        gen_stub(30, "MC_knlSetSystemProperty"),
+        set_system_property.into_body(),
         get_resource_id.into_body(),
         get_resource.into_body(),
         reserved1.into_body(),
@@ -0,0 +1,116 @@
+use alloc::{boxed::Box, string::String};
+use core::future::Future;
+
+use crate::base::{WIPICContext, WIPICMethodBody, WIPICWord};
+
+/// A single WIPI-C method, callable with raw `WIPICWord` arguments straight off the method table.
+#[async_trait::async_trait(?Send)]
+pub trait MethodBody<E> {
+    async fn call(&self, context: &mut dyn WIPICContext, args: &[WIPICWord]) -> Result<WIPICWord, E>;
+}
+
+/// Converts a single argument or return value between its raw `WIPICWord` form on the method table
+/// and the Rust type a kernel function actually wants to work with.
+pub trait TypeConverter<T> {
+    fn to_rust(context: &mut dyn WIPICContext, raw: WIPICWord) -> T;
+    fn from_rust(context: &mut dyn WIPICContext, rust: T) -> WIPICWord;
+}
+
+impl TypeConverter<WIPICWord> for WIPICWord {
+    fn to_rust(_: &mut dyn WIPICContext, raw: WIPICWord) -> WIPICWord {
+        raw
+    }
+
+    fn from_rust(_: &mut dyn WIPICContext, rust: WIPICWord) -> WIPICWord {
+        rust
+    }
+}
+
+impl TypeConverter<i32> for i32 {
+    fn to_rust(_: &mut dyn WIPICContext, raw: WIPICWord) -> i32 {
+        raw as i32
+    }
+
+    fn from_rust(_: &mut dyn WIPICContext, rust: i32) -> WIPICWord {
+        rust as WIPICWord
+    }
+}
+
+impl TypeConverter<()> for () {
+    fn to_rust(_: &mut dyn WIPICContext, _raw: WIPICWord) {}
+
+    fn from_rust(_: &mut dyn WIPICContext, _rust: ()) -> WIPICWord {
+        0
+    }
+}
+
+impl TypeConverter<String> for String {
+    fn to_rust(context: &mut dyn WIPICContext, raw: WIPICWord) -> String {
+        wie_base::util::read_null_terminated_string(context, raw).unwrap_or_default()
+    }
+
+    fn from_rust(_: &mut dyn WIPICContext, _rust: String) -> WIPICWord {
+        unimplemented!("String is only used as a kernel method parameter, never a return value")
+    }
+}
+
+impl TypeConverter<crate::base::WIPICMemoryId> for crate::base::WIPICMemoryId {
+    fn to_rust(_: &mut dyn WIPICContext, raw: WIPICWord) -> crate::base::WIPICMemoryId {
+        crate::base::WIPICMemoryId(raw)
+    }
+
+    fn from_rust(_: &mut dyn WIPICContext, rust: crate::base::WIPICMemoryId) -> WIPICWord {
+        rust.0
+    }
+}
+
+/// Turns a plain async fn taking `(&mut dyn WIPICContext, ...)` into a `WIPICMethodBody`, converting
+/// each raw `WIPICWord` argument to its Rust type via `TypeConverter` and the returned value back.
+pub trait MethodImpl<F, R, E, P> {
+    fn into_body(self) -> WIPICMethodBody;
+}
+
+macro_rules! impl_method_body {
+    ($($arg:ident: $arg_ty:ident),*) => {
+        #[allow(non_snake_case, unused_variables, unused_mut)]
+        impl<F, Fut, R, $($arg_ty,)*> MethodImpl<F, R, crate::base::WIPICError, ($($arg_ty,)*)> for F
+        where
+            F: Fn(&mut dyn WIPICContext, $($arg_ty,)*) -> Fut + 'static,
+            Fut: Future<Output = Result<R, crate::base::WIPICError>>,
+            R: TypeConverter<R>,
+            $($arg_ty: TypeConverter<$arg_ty>,)*
+        {
+            fn into_body(self) -> WIPICMethodBody {
+                struct Holder<F, Fut, R, $($arg_ty,)*>(F, core::marker::PhantomData<(Fut, R, $($arg_ty,)*)>);
+
+                #[async_trait::async_trait(?Send)]
+                impl<F, Fut, R, $($arg_ty,)*> MethodBody<crate::base::WIPICError> for Holder<F, Fut, R, $($arg_ty,)*>
+                where
+                    F: Fn(&mut dyn WIPICContext, $($arg_ty,)*) -> Fut,
+                    Fut: Future<Output = Result<R, crate::base::WIPICError>>,
+                    R: TypeConverter<R>,
+                    $($arg_ty: TypeConverter<$arg_ty>,)*
+                {
+                    async fn call(&self, context: &mut dyn WIPICContext, args: &[WIPICWord]) -> Result<WIPICWord, crate::base::WIPICError> {
+                        let mut raw_args = args.iter().copied();
+                        $(
+                            let $arg = <$arg_ty as TypeConverter<$arg_ty>>::to_rust(context, raw_args.next().unwrap_or(0));
+                        )*
+
+                        let result = (self.0)(context, $($arg,)*).await?;
+
+                        Ok(<R as TypeConverter<R>>::from_rust(context, result))
+                    }
+                }
+
+                Box::new(Holder(self, core::marker::PhantomData))
+            }
+        }
+    };
+}
+
+impl_method_body!();
+impl_method_body!(a1: A1);
+impl_method_body!(a1: A1, a2: A2);
+impl_method_body!(a1: A1, a2: A2, a3: A3);
+impl_method_body!(a1: A1, a2: A2, a3: A3, a4: A4);